@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+
+const K1: f32 = 1.2;
+const B: f32 = 0.75;
+
+/// A BM25 keyword index over a fixed set of documents, built once and
+/// queried as many times as needed.
+pub struct Bm25Index {
+    /// Term frequency per document, keyed by document index.
+    term_frequencies: Vec<HashMap<String, usize>>,
+    /// Number of documents containing each term.
+    document_frequencies: HashMap<String, usize>,
+    document_lengths: Vec<usize>,
+    average_document_length: f32,
+}
+
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(|t| t.to_lowercase())
+        .collect()
+}
+
+impl Bm25Index {
+    /// Builds an index over `documents`, where the index of each document
+    /// matches the index callers will get back from `search`.
+    pub fn build(documents: &[String]) -> Self {
+        let mut term_frequencies = Vec::with_capacity(documents.len());
+        let mut document_frequencies: HashMap<String, usize> = HashMap::new();
+        let mut document_lengths = Vec::with_capacity(documents.len());
+
+        for document in documents {
+            let tokens = tokenize(document);
+            document_lengths.push(tokens.len());
+
+            let mut counts: HashMap<String, usize> = HashMap::new();
+            for token in tokens {
+                *counts.entry(token).or_insert(0) += 1;
+            }
+            for term in counts.keys() {
+                *document_frequencies.entry(term.clone()).or_insert(0) += 1;
+            }
+            term_frequencies.push(counts);
+        }
+
+        let average_document_length = if document_lengths.is_empty() {
+            0.0
+        } else {
+            document_lengths.iter().sum::<usize>() as f32 / document_lengths.len() as f32
+        };
+
+        Self {
+            term_frequencies,
+            document_frequencies,
+            document_lengths,
+            average_document_length,
+        }
+    }
+
+    fn idf(&self, term: &str) -> f32 {
+        let n = self.term_frequencies.len() as f32;
+        let df = *self.document_frequencies.get(term).unwrap_or(&0) as f32;
+        ((n - df + 0.5) / (df + 0.5) + 1.0).ln()
+    }
+
+    /// Scores every document against `query`, returning `(index, score)`
+    /// pairs sorted by descending score.
+    pub fn search(&self, query: &str) -> Vec<(usize, f32)> {
+        let query_terms = tokenize(query);
+
+        let mut scores: Vec<(usize, f32)> = (0..self.term_frequencies.len())
+            .map(|doc_index| {
+                let doc_len = self.document_lengths[doc_index] as f32;
+                let score = query_terms
+                    .iter()
+                    .map(|term| {
+                        let freq = *self.term_frequencies[doc_index]
+                            .get(term)
+                            .unwrap_or(&0) as f32;
+                        if freq == 0.0 {
+                            return 0.0;
+                        }
+                        let idf = self.idf(term);
+                        idf * (freq * (K1 + 1.0))
+                            / (freq + K1 * (1.0 - B + B * doc_len / self.average_document_length))
+                    })
+                    .sum();
+                (doc_index, score)
+            })
+            .collect();
+
+        scores.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+        scores
+    }
+}
+
+/// Fuses ranked result lists with reciprocal rank fusion: each document's
+/// score is the sum of `1 / (k + rank)` over every list it appears in,
+/// where `rank` is its 1-based position in that list.
+pub fn reciprocal_rank_fusion(rankings: &[Vec<usize>], k: f32) -> Vec<(usize, f32)> {
+    let mut scores: HashMap<usize, f32> = HashMap::new();
+    for ranking in rankings {
+        for (rank, &doc_index) in ranking.iter().enumerate() {
+            *scores.entry(doc_index).or_insert(0.0) += 1.0 / (k + (rank + 1) as f32);
+        }
+    }
+    let mut fused: Vec<(usize, f32)> = scores.into_iter().collect();
+    fused.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+    fused
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranks_documents_containing_the_query_term_above_those_without_it() {
+        let index = Bm25Index::build(&[
+            "the quick brown fox".to_string(),
+            "the lazy dog".to_string(),
+        ]);
+
+        let results = index.search("fox");
+        assert_eq!(results[0].0, 0);
+        assert!(results[0].1 > results[1].1);
+        assert_eq!(results[1].1, 0.0);
+    }
+
+    #[test]
+    fn rarer_terms_score_higher_than_common_ones() {
+        let index = Bm25Index::build(&[
+            "rust rust rust".to_string(),
+            "rust python".to_string(),
+            "rust go".to_string(),
+        ]);
+
+        // "rust" appears in every document, so its idf is near zero; a term
+        // unique to one document should outweigh it for that document.
+        let common_only = index.search("rust")[0].1;
+        let with_rare_term = index
+            .search("python")
+            .into_iter()
+            .find(|(doc, _)| *doc == 1)
+            .unwrap()
+            .1;
+        assert!(with_rare_term > common_only);
+    }
+
+    #[test]
+    fn reciprocal_rank_fusion_rewards_documents_ranked_highly_in_multiple_lists() {
+        let rankings = vec![vec![0, 1, 2], vec![1, 0, 2]];
+        let fused = reciprocal_rank_fusion(&rankings, 60.0);
+
+        // Documents 0 and 1 each lead one list, so they should outrank the
+        // document that's last in both.
+        let doc2_score = fused.iter().find(|(doc, _)| *doc == 2).unwrap().1;
+        for &doc in &[0usize, 1] {
+            let score = fused.iter().find(|(d, _)| *d == doc).unwrap().1;
+            assert!(score > doc2_score);
+        }
+    }
+}
@@ -0,0 +1,305 @@
+use text_splitter::TextSplitter;
+use tokenizers::Tokenizer;
+use tree_sitter::{Language, Node, Parser};
+
+/// One chunk of source text aligned to syntactic boundaries (or, for
+/// unrecognized file types and oversized nodes, to the token splitter's
+/// boundaries), ready to be embedded.
+pub struct CodeChunk {
+    pub start_byte: usize,
+    pub end_byte: usize,
+    pub start_line: usize,
+    pub end_line: usize,
+    /// The function/method/struct/enum identifier the chunk was built from, if any.
+    pub symbol: Option<String>,
+    pub text: String,
+}
+
+/// Leaf declaration node kinds worth chunking on their own, and the field
+/// that holds their name, per supported language. Any other node kind
+/// (`impl`/`trait` blocks, class bodies, the file root itself) is walked
+/// through transparently so declarations nested inside them — methods in
+/// particular — are still found and chunked individually.
+struct LanguageProfile {
+    language: fn() -> Language,
+    item_kinds: &'static [&'static str],
+    name_field: &'static str,
+}
+
+fn profile_for_extension(extension: &str) -> Option<LanguageProfile> {
+    match extension {
+        "rs" => Some(LanguageProfile {
+            language: tree_sitter_rust::language,
+            item_kinds: &["function_item", "struct_item", "enum_item"],
+            name_field: "name",
+        }),
+        "py" => Some(LanguageProfile {
+            language: tree_sitter_python::language,
+            item_kinds: &["function_definition"],
+            name_field: "name",
+        }),
+        "js" | "jsx" | "ts" | "tsx" => Some(LanguageProfile {
+            language: tree_sitter_javascript::language,
+            item_kinds: &["function_declaration", "method_definition"],
+            name_field: "name",
+        }),
+        _ => None,
+    }
+}
+
+/// Splits `content` into chunks. Recognized source extensions are parsed
+/// with tree-sitter and chunked along declaration boundaries (including
+/// methods nested inside `impl` blocks and classes);
+/// everything else (and any declaration too large on its own) falls back to
+/// `fallback_splitter`, a plain token-budget splitter.
+pub fn chunk_file(
+    extension: &str,
+    content: &str,
+    fallback_splitter: &TextSplitter<Tokenizer>,
+) -> Vec<CodeChunk> {
+    match profile_for_extension(extension) {
+        Some(profile) => chunk_with_tree_sitter(&profile, content, fallback_splitter)
+            .unwrap_or_else(|| chunk_with_fallback(content, fallback_splitter)),
+        None => chunk_with_fallback(content, fallback_splitter),
+    }
+}
+
+fn chunk_with_tree_sitter(
+    profile: &LanguageProfile,
+    content: &str,
+    fallback_splitter: &TextSplitter<Tokenizer>,
+) -> Option<Vec<CodeChunk>> {
+    let mut parser = Parser::new();
+    parser.set_language(&(profile.language)()).ok()?;
+    let tree = parser.parse(content, None)?;
+    let root = tree.root_node();
+
+    let mut items = Vec::new();
+    collect_items(root, profile, &mut items);
+
+    // No item-level declarations matched anywhere in the tree: leave this
+    // to the plain fallback splitter rather than claiming the whole file
+    // is "chunked" into nothing.
+    if items.is_empty() {
+        return None;
+    }
+
+    let mut chunks = Vec::new();
+    let mut gap_start = 0usize;
+    for item in &items {
+        chunks.extend(chunk_gap(content, gap_start, item.start_byte(), fallback_splitter));
+        chunks.extend(chunk_node(item, profile, content, fallback_splitter));
+        gap_start = item.end_byte();
+    }
+    // Trailing content after the last matched item (or between it and the
+    // next one) is still part of the file and must not be silently dropped.
+    chunks.extend(chunk_gap(content, gap_start, content.len(), fallback_splitter));
+
+    Some(chunks)
+}
+
+/// Walks `node`'s descendants in document order, collecting every node
+/// whose kind is a leaf declaration (`profile.item_kinds`). Non-matching
+/// nodes — `impl`/`trait` blocks, class bodies, the root itself — are
+/// descended into transparently, so e.g. a method nested inside an `impl`
+/// block is still found and chunked on its own.
+fn collect_items<'tree>(node: Node<'tree>, profile: &LanguageProfile, out: &mut Vec<Node<'tree>>) {
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        if profile.item_kinds.contains(&child.kind()) {
+            out.push(child);
+        } else {
+            collect_items(child, profile, out);
+        }
+    }
+}
+
+/// Chunks `content[start..end]` with the fallback splitter, translating
+/// offsets back into file space, for content between (or after)
+/// the declarations `chunk_with_tree_sitter` matched on.
+fn chunk_gap(
+    content: &str,
+    start: usize,
+    end: usize,
+    fallback_splitter: &TextSplitter<Tokenizer>,
+) -> Vec<CodeChunk> {
+    if start >= end || content[start..end].trim().is_empty() {
+        return Vec::new();
+    }
+    chunk_with_fallback(&content[start..end], fallback_splitter)
+        .into_iter()
+        .map(|chunk| CodeChunk {
+            start_byte: start + chunk.start_byte,
+            end_byte: start + chunk.end_byte,
+            start_line: content[..start].lines().count() + chunk.start_line,
+            end_line: content[..start].lines().count() + chunk.end_line,
+            symbol: None,
+            text: chunk.text,
+        })
+        .collect()
+}
+
+fn chunk_node(
+    node: &Node,
+    profile: &LanguageProfile,
+    content: &str,
+    fallback_splitter: &TextSplitter<Tokenizer>,
+) -> Vec<CodeChunk> {
+    let text = &content[node.start_byte()..node.end_byte()];
+    let symbol = node
+        .child_by_field_name(profile.name_field)
+        .and_then(|name| name.utf8_text(content.as_bytes()).ok())
+        .map(|s| s.to_string());
+
+    // Oversized declarations (a very long function, for example) still get
+    // split by the token splitter, but the resulting pieces keep the node's
+    // symbol name and have their offsets translated back into file space.
+    let pieces: Vec<&str> = fallback_splitter.chunks(text).collect();
+    if pieces.len() <= 1 {
+        return vec![CodeChunk {
+            start_byte: node.start_byte(),
+            end_byte: node.end_byte(),
+            start_line: node.start_position().row + 1,
+            end_line: node.end_position().row + 1,
+            symbol,
+            text: text.to_string(),
+        }];
+    }
+
+    // text-splitter doesn't guarantee its pieces are a contiguous,
+    // gap-free tiling of `text` (it may trim whitespace at piece
+    // boundaries), so each piece's real position has to be located by
+    // searching forward from the end of the previous one, the same way
+    // `chunk_with_fallback` does for the whole file.
+    let mut offset = 0usize;
+    let base_line = node.start_position().row + 1;
+    pieces
+        .into_iter()
+        .map(|piece| {
+            let piece_start = text[offset..]
+                .find(piece)
+                .map(|found| offset + found)
+                .unwrap_or(offset);
+            let piece_end = piece_start + piece.len();
+            let lines_before = text[..piece_start].lines().count();
+            offset = piece_end;
+            CodeChunk {
+                start_byte: node.start_byte() + piece_start,
+                end_byte: node.start_byte() + piece_end,
+                start_line: base_line + lines_before,
+                end_line: base_line + lines_before + piece.lines().count().saturating_sub(1),
+                symbol: symbol.clone(),
+                text: piece.to_string(),
+            }
+        })
+        .collect()
+}
+
+fn chunk_with_fallback(
+    content: &str,
+    fallback_splitter: &TextSplitter<Tokenizer>,
+) -> Vec<CodeChunk> {
+    let mut offset = 0usize;
+    fallback_splitter
+        .chunks(content)
+        .map(|piece| {
+            let start_byte = content[offset..]
+                .find(piece)
+                .map(|found| offset + found)
+                .unwrap_or(offset);
+            let end_byte = start_byte + piece.len();
+            let start_line = content[..start_byte].lines().count() + 1;
+            let end_line = content[..end_byte].lines().count().max(start_line);
+            offset = end_byte;
+            CodeChunk {
+                start_byte,
+                end_byte,
+                start_line,
+                end_line,
+                symbol: None,
+                text: piece.to_string(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use text_splitter::ChunkConfig;
+    use tokenizers::models::wordlevel::WordLevel;
+    use tokenizers::pre_tokenizers::whitespace::Whitespace;
+
+    /// A splitter that counts whitespace-separated words as tokens, so tests
+    /// can force a split by picking a small `max_tokens` budget.
+    fn splitter(max_tokens: usize) -> TextSplitter<Tokenizer> {
+        let mut vocab = HashMap::new();
+        vocab.insert("[UNK]".to_string(), 0u32);
+        let model = WordLevel::builder()
+            .vocab(vocab)
+            .unk_token("[UNK]".to_string())
+            .build()
+            .unwrap();
+        let mut tokenizer = Tokenizer::new(model);
+        tokenizer.with_pre_tokenizer(Whitespace::default());
+        TextSplitter::new(ChunkConfig::new(max_tokens).with_sizer(tokenizer))
+    }
+
+    #[test]
+    fn chunks_rust_functions_with_their_symbol_name() {
+        let content = "fn add(a: i32, b: i32) -> i32 {\n    a + b\n}\n";
+        let chunks = chunk_file("rs", content, &splitter(1000));
+
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].symbol.as_deref(), Some("add"));
+        assert_eq!(&content[chunks[0].start_byte..chunks[0].end_byte], content.trim_end());
+    }
+
+    #[test]
+    fn includes_non_item_top_level_content_alongside_matched_items() {
+        let content = "use std::fmt;\n\nfn greet() {\n    println!(\"hi\");\n}\n";
+        let chunks = chunk_file("rs", content, &splitter(1000));
+
+        assert!(chunks.iter().any(|c| c.symbol.as_deref() == Some("greet")));
+        assert!(chunks
+            .iter()
+            .any(|c| c.symbol.is_none() && c.text.contains("use std::fmt;")));
+    }
+
+    #[test]
+    fn falls_back_to_the_plain_splitter_when_nothing_matches() {
+        // A file with no top-level function/struct/etc declarations at all.
+        let content = "// just a comment, no items here\n";
+        let chunks = chunk_file("rs", content, &splitter(1000));
+
+        assert_eq!(chunks.len(), 1);
+        assert!(chunks[0].symbol.is_none());
+        assert_eq!(chunks[0].text, content.trim_end());
+    }
+
+    #[test]
+    fn chunks_methods_nested_inside_an_impl_block_individually() {
+        let content = "impl Foo {\n    fn bar() {}\n    fn baz() {}\n}\n";
+        let chunks = chunk_file("rs", content, &splitter(1000));
+
+        // The `impl Foo {` header and the closing brace are kept as
+        // symbol-less gap chunks around the two methods.
+        assert_eq!(chunks.len(), 4);
+        assert_eq!(chunks[1].symbol.as_deref(), Some("bar"));
+        assert_eq!(chunks[2].symbol.as_deref(), Some("baz"));
+        assert!(chunks[0].symbol.is_none() && chunks[0].text.contains("impl Foo"));
+    }
+
+    #[test]
+    fn oversized_declarations_are_split_with_offsets_that_round_trip() {
+        let content = "fn big() {\n    one two three four five six\n}\n";
+        let chunks = chunk_file("rs", content, &splitter(2));
+
+        assert!(chunks.len() > 1);
+        for chunk in &chunks {
+            assert_eq!(&content[chunk.start_byte..chunk.end_byte], chunk.text);
+            assert_eq!(chunk.symbol.as_deref(), Some("big"));
+        }
+    }
+}
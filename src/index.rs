@@ -0,0 +1,226 @@
+use crate::chunker::chunk_file;
+use crate::provider::{build_provider, validate_dimensions};
+use crate::queue::{EmbeddingQueue, QueueItem};
+use crate::store::{PendingSpan, Store};
+use glob::glob;
+use std::{collections::HashMap, ffi::OsStr, fs, path::PathBuf, time::Duration};
+use text_splitter::{ChunkConfig, TextSplitter};
+use tokenizers::Tokenizer;
+
+/// Parameters shared by a one-shot index run and a `--watch` loop.
+pub struct IndexConfig {
+    pub pattern: String,
+    pub provider: String,
+    pub model: String,
+    pub max_batch_tokens: usize,
+}
+
+struct Document {
+    path: PathBuf,
+    mtime: i64,
+    text: String,
+    start_byte: usize,
+    end_byte: usize,
+    start_line: usize,
+    end_line: usize,
+    symbol: Option<String>,
+    digest: String,
+}
+
+/// Chunks and embeds every file matching `config.pattern` in a single pass
+/// (no intermediate files), skipping files whose mtime hasn't changed since
+/// the last run. Returns the number of files that were (re)indexed.
+pub fn index_once(
+    db: &mut Store,
+    config: &IndexConfig,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let embedder = build_provider(&config.provider, &config.model)?;
+    db.check_or_set_provider(&embedder.name())?;
+
+    let tokenizer = Tokenizer::from_pretrained("bert-base-cased", None).unwrap();
+    let splitter = TextSplitter::new(ChunkConfig::new(1000).with_sizer(tokenizer.clone()));
+
+    let mut documents = Vec::new();
+    let mut reindexed_files = 0usize;
+    for entry in glob(&config.pattern)? {
+        let path = entry?;
+        let mtime = fs::metadata(&path)?
+            .modified()?
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64;
+
+        if db.file_mtime(&path.to_string_lossy())? == Some(mtime) {
+            continue;
+        }
+        reindexed_files += 1;
+
+        let base_name = path.file_name().unwrap_or(OsStr::new("unknown"));
+        let extension = path
+            .extension()
+            .unwrap_or(OsStr::new("txt"))
+            .to_string_lossy();
+        let content = fs::read_to_string(&path)?;
+
+        for chunk in chunk_file(&extension, &content, &splitter) {
+            // Digest the raw chunk text, not the "From: ..." header added
+            // below, so identical code in differently named files still
+            // shares one cached embedding.
+            let digest = Store::digest(&chunk.text);
+            let text = match &chunk.symbol {
+                Some(symbol) => format!("From: {base_name:?} ({symbol})\n{}", chunk.text),
+                None => format!("From: {base_name:?}\n{}", chunk.text),
+            };
+            documents.push(Document {
+                path: path.clone(),
+                mtime,
+                text,
+                start_byte: chunk.start_byte,
+                end_byte: chunk.end_byte,
+                start_line: chunk.start_line,
+                end_line: chunk.end_line,
+                symbol: chunk.symbol,
+                digest,
+            });
+        }
+    }
+
+    if documents.is_empty() {
+        return Ok(reindexed_files);
+    }
+
+    let queue = EmbeddingQueue::new(tokenizer, config.max_batch_tokens);
+    let items: Vec<QueueItem> = documents
+        .iter()
+        .enumerate()
+        .map(|(index, doc)| QueueItem {
+            index,
+            text: doc.text.clone(),
+        })
+        .collect();
+    let batches = queue.batch(items);
+
+    // Resolve every batch's embeddings (reusing cached ones by digest)
+    // before touching the store, so the digest cache still sees this run's
+    // own unchanged spans.
+    let mut pending_by_batch = Vec::with_capacity(batches.len());
+    for batch in &batches {
+        let mut embeddings: Vec<Option<Vec<f32>>> = Vec::with_capacity(batch.texts.len());
+        let mut uncached_texts = Vec::new();
+        let mut uncached_slots = Vec::new();
+        for (slot, text) in batch.texts.iter().enumerate() {
+            // Every document folded into this slot shares identical text,
+            // which (since the digest is over the raw chunk body) means
+            // they share the same digest too; any one of them will do.
+            let digest = &documents[batch.occurrences[slot][0]].digest;
+            match db.find_by_digest(digest)? {
+                Some(cached) => embeddings.push(Some(cached)),
+                None => {
+                    embeddings.push(None);
+                    uncached_texts.push(text.clone());
+                    uncached_slots.push(slot);
+                }
+            }
+        }
+        if !uncached_texts.is_empty() {
+            let fresh = embedder.embed(uncached_texts)?;
+            validate_dimensions(embedder.as_ref(), &fresh)?;
+            for (slot, embedding) in uncached_slots.into_iter().zip(fresh) {
+                embeddings[slot] = Some(embedding);
+            }
+        }
+
+        let mut pending = Vec::new();
+        for (embedding, indices) in embeddings.into_iter().zip(&batch.occurrences) {
+            let embedding = embedding.expect("every slot is filled by cache or provider");
+            for &index in indices {
+                let doc = &documents[index];
+                pending.push(PendingSpan {
+                    path: doc.path.to_string_lossy().into_owned(),
+                    start_byte: doc.start_byte,
+                    end_byte: doc.end_byte,
+                    start_line: doc.start_line,
+                    end_line: doc.end_line,
+                    digest: doc.digest.clone(),
+                    text: doc.text.clone(),
+                    symbol: doc.symbol.clone(),
+                    embedding: embedding.clone(),
+                });
+            }
+        }
+        pending_by_batch.push(pending);
+    }
+
+    let touched_files: HashMap<String, i64> = documents
+        .into_iter()
+        .map(|doc| (doc.path.to_string_lossy().into_owned(), doc.mtime))
+        .collect();
+    db.clear_paths(touched_files.keys().map(|p| p.as_str()))?;
+
+    for pending in &pending_by_batch {
+        db.commit_batch(pending)?;
+    }
+
+    // Only now, with every batch for every touched file committed, is it
+    // safe to record these files as fully indexed.
+    let files: Vec<(String, i64)> = touched_files.into_iter().collect();
+    db.mark_files_indexed(&files)?;
+
+    Ok(reindexed_files)
+}
+
+/// Re-runs `index_once` on a debounce, picking up file changes by mtime as
+/// they happen. Never returns under normal operation.
+pub fn watch(
+    db: &mut Store,
+    config: &IndexConfig,
+    debounce: Duration,
+) -> Result<(), Box<dyn std::error::Error>> {
+    loop {
+        let reindexed = index_once(db, config)?;
+        if reindexed > 0 {
+            println!("Reindexed {reindexed} changed file(s).");
+        }
+        std::thread::sleep(debounce);
+    }
+}
+
+/// One row of `index status` output.
+pub struct FileStatus {
+    pub path: String,
+    pub chunk_count: usize,
+    /// The file's mtime no longer matches what's stored, so its spans are
+    /// out of date.
+    pub stale: bool,
+    /// The file no longer exists on disk but is still indexed.
+    pub missing: bool,
+}
+
+/// Lists every indexed path along with its chunk count and whether it's
+/// gone missing or fallen stale since it was last indexed.
+pub fn status(db: &Store) -> Result<Vec<FileStatus>, Box<dyn std::error::Error>> {
+    db.file_summaries()?
+        .into_iter()
+        .map(|(path, mtime, chunk_count)| {
+            let metadata = fs::metadata(&path);
+            let missing = metadata.is_err();
+            let stale = match metadata {
+                Ok(metadata) => {
+                    let current_mtime = metadata
+                        .modified()?
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs() as i64;
+                    current_mtime != mtime
+                }
+                Err(_) => false,
+            };
+            Ok(FileStatus {
+                path,
+                chunk_count,
+                stale,
+                missing,
+            })
+        })
+        .collect()
+}
@@ -1,12 +1,37 @@
-use clap::{Parser, Subcommand};
-use fastembed::{EmbeddingModel, InitOptions, TextEmbedding};
+use clap::{Parser, Subcommand, ValueEnum};
 use glob::glob;
 use indicatif::{ProgressBar, ProgressFinish, ProgressStyle};
-use serde_json::{from_reader, to_writer};
 use std::{ffi::OsStr, fs, path::PathBuf};
 use text_splitter::{ChunkConfig, TextSplitter};
 use tokenizers::Tokenizer;
 
+mod bm25;
+mod chunker;
+mod index;
+mod provider;
+mod queue;
+mod store;
+use bm25::{reciprocal_rank_fusion, Bm25Index};
+use chunker::chunk_file;
+use index::IndexConfig;
+use provider::{build_provider, validate_dimensions};
+use store::Store;
+
+/// Which signal(s) `Query` ranks results by.
+#[derive(Clone, ValueEnum)]
+enum SearchMode {
+    /// Rank purely by cosine similarity between query and span embeddings
+    Vector,
+    /// Rank purely by BM25 over the stored span text
+    Keyword,
+    /// Fuse vector and keyword rankings with reciprocal rank fusion
+    Hybrid,
+}
+
+/// Reciprocal rank fusion constant; higher values flatten the influence of
+/// rank position, as in the original RRF paper.
+const RRF_K: f32 = 60.0;
+
 #[derive(Parser)]
 #[command(version, about)]
 struct Args {
@@ -28,18 +53,77 @@ enum Command {
     Embed {
         /// Glob pattern for files to process
         pattern: String,
-        /// Output JSON file for embeddings
-        output: PathBuf,
+        /// SQLite database to write embeddings to
+        store: PathBuf,
+
+        /// Embedding backend to use
+        #[arg(long, default_value = "fastembed")]
+        provider: String,
+        /// Model name understood by the chosen provider
+        #[arg(long, default_value = "AllMiniLML6V2")]
+        model: String,
+        /// Maximum summed token count per embedding batch
+        #[arg(long, default_value_t = 2000)]
+        max_batch_tokens: usize,
     },
     /// Query similar documents
     Query {
-        /// Input JSON file with embeddings
-        input: PathBuf,
+        /// SQLite database with embeddings
+        store: PathBuf,
         /// Query text
         query: String,
 
         #[arg(short, long, default_value_t = 1)]
         top_k: usize,
+
+        /// Embedding backend to use; must match the one the store was embedded with
+        #[arg(long, default_value = "fastembed")]
+        provider: String,
+        /// Model name understood by the chosen provider
+        #[arg(long, default_value = "AllMiniLML6V2")]
+        model: String,
+
+        /// Which signal(s) to rank results by
+        #[arg(long, value_enum, default_value = "hybrid")]
+        mode: SearchMode,
+    },
+    /// Chunk, embed and link files into the store in a single pass
+    Index {
+        #[command(subcommand)]
+        action: IndexAction,
+    },
+}
+
+#[derive(Subcommand)]
+enum IndexAction {
+    /// Chunk and embed matching files, skipping any that haven't changed
+    Run {
+        /// Glob pattern for files to process
+        pattern: String,
+        /// SQLite database to write embeddings to
+        store: PathBuf,
+
+        /// Embedding backend to use
+        #[arg(long, default_value = "fastembed")]
+        provider: String,
+        /// Model name understood by the chosen provider
+        #[arg(long, default_value = "AllMiniLML6V2")]
+        model: String,
+        /// Maximum summed token count per embedding batch
+        #[arg(long, default_value_t = 2000)]
+        max_batch_tokens: usize,
+
+        /// Keep running, eagerly reindexing changed files on a debounce
+        #[arg(long)]
+        watch: bool,
+        /// Debounce between watch passes, in milliseconds
+        #[arg(long, default_value_t = 500)]
+        debounce_ms: u64,
+    },
+    /// List indexed paths, their chunk counts, and any that are stale or missing
+    Status {
+        /// SQLite database to inspect
+        store: PathBuf,
     },
 }
 
@@ -52,15 +136,6 @@ fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
-    let model = TextEmbedding::try_new(
-        InitOptions::new(EmbeddingModel::AllMiniLML6V2)
-            .with_show_download_progress(true)
-            .with_cache_dir(
-                dirs::cache_dir()
-                    .expect("Could not get cache dir")
-                    .join("emqu-models"),
-            ),
-    )?;
 
     fn get_progress(len: u64, message: &'static str) -> ProgressBar {
         ProgressBar::new(len)
@@ -98,78 +173,242 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     .unwrap_or(OsStr::new("txt"))
                     .to_string_lossy();
 
-                let chunks = splitter.chunks(&content).collect::<Vec<_>>();
-                let line_counts: Vec<(usize, usize)> = chunks
-                    .iter()
-                    .scan(0, |acc, chunk| {
-                        let lines = chunk.lines().count();
-                        let start = *acc + 1;
-                        *acc += lines;
-                        Some((start, *acc))
-                    })
-                    .collect();
+                let chunks = chunk_file(&extension, &content, &splitter);
 
-                for (i, (chunk, (start, end))) in chunks.iter().zip(line_counts).enumerate() {
-                    let header = format!("From {}, lines {} - {}\n\n", base_name, start, end);
-                    let chunk_file = output.join(format!("{}-{}.{}", base_name, i + 1, extension));
-                    fs::write(chunk_file, header + chunk)?;
+                for (i, chunk) in chunks.iter().enumerate() {
+                    let header = match &chunk.symbol {
+                        Some(symbol) => format!(
+                            "From {}, lines {} - {} ({})\n\n",
+                            base_name, chunk.start_line, chunk.end_line, symbol
+                        ),
+                        None => format!(
+                            "From {}, lines {} - {}\n\n",
+                            base_name, chunk.start_line, chunk.end_line
+                        ),
+                    };
+                    let chunk_file_path =
+                        output.join(format!("{}-{}.{}", base_name, i + 1, extension));
+                    fs::write(chunk_file_path, header + &chunk.text)?;
                 }
                 progress.inc(1u64);
             }
 
             println!("Successfully chunked documents into {}", output.display());
         }
-        Command::Embed { pattern, output } => {
-            let mut documents = Vec::new();
-
-            let embed_files: Vec<_> = glob(&pattern)?.collect();
-            println!("Embedding {} document(s).", embed_files.len());
-
-            for entry in embed_files {
-                let path = entry?;
-                let base_name = path.file_name().unwrap_or(OsStr::new("unknown"));
-                let content = fs::read_to_string(&path)?;
-                documents.push(format!("From: {base_name:?}\n{content}"));
-            }
-
-            let embeddings = model.embed(documents.clone(), None)?;
-            let output_data: Vec<(String, Vec<f32>)> =
-                documents.into_iter().zip(embeddings).collect();
-
-            let file = fs::File::create(output)?;
-            to_writer(file, &output_data)?;
+        Command::Embed {
+            pattern,
+            store,
+            provider,
+            model,
+            max_batch_tokens,
+        } => {
+            let mut db = Store::open(&store)?;
+            let config = IndexConfig {
+                pattern,
+                provider,
+                model,
+                max_batch_tokens,
+            };
+            index::index_once(&mut db, &config)?;
 
-            println!(
-                "Successfully generated embeddings for {} documents",
-                output_data.len()
-            );
+            println!("Successfully generated embeddings into {}", store.display());
         }
         Command::Query {
-            input,
+            store,
             query,
             top_k,
+            provider,
+            model,
+            mode,
         } => {
-            let file = fs::File::open(input)?;
-            let stored_embeddings: Vec<(String, Vec<f32>)> = from_reader(file)?;
+            let db = Store::open(&store)?;
+
+            fn print_hit(
+                path: &str,
+                start_line: usize,
+                end_line: usize,
+                start_byte: usize,
+                end_byte: usize,
+                symbol: &Option<String>,
+            ) {
+                match symbol {
+                    Some(symbol) => println!(
+                        "{path}:{start_line}-{end_line} (bytes {start_byte}-{end_byte}) ({symbol})\n"
+                    ),
+                    None => println!("{path}:{start_line}-{end_line} (bytes {start_byte}-{end_byte})\n"),
+                }
+            }
+
+            // Vector-only ranking never needs span text, so score spans as
+            // they stream from the store instead of materializing every
+            // span's text up front. Keyword and hybrid ranking both need
+            // the whole corpus's text at once (BM25 scores relative to
+            // every document), so they fall through to the materialized path.
+            if let SearchMode::Vector = mode {
+                let embedder = build_provider(&provider, &model)?;
+                db.check_or_set_provider(&embedder.name())?;
+                let query_embeddings = embedder.embed(vec![query.clone()])?;
+                validate_dimensions(embedder.as_ref(), &query_embeddings)?;
+                let query_embedding = &query_embeddings[0];
+
+                struct Hit {
+                    path: String,
+                    start_line: usize,
+                    end_line: usize,
+                    start_byte: usize,
+                    end_byte: usize,
+                    symbol: Option<String>,
+                }
+                let mut top: Vec<(f32, Hit)> = Vec::with_capacity(top_k);
+                db.for_each_span(|span| {
+                    let score = cosine_similarity(query_embedding, &span.embedding);
+                    let position = top.partition_point(|(existing, _)| *existing >= score);
+                    if position < top_k {
+                        top.insert(
+                            position,
+                            (
+                                score,
+                                Hit {
+                                    path: span.path,
+                                    start_line: span.start_line,
+                                    end_line: span.end_line,
+                                    start_byte: span.start_byte,
+                                    end_byte: span.end_byte,
+                                    symbol: span.symbol,
+                                },
+                            ),
+                        );
+                        top.truncate(top_k);
+                    }
+                })?;
 
-            let query_embedding = &model.embed(vec![query], None)?[0];
+                for (_, hit) in &top {
+                    print_hit(
+                        &hit.path,
+                        hit.start_line,
+                        hit.end_line,
+                        hit.start_byte,
+                        hit.end_byte,
+                        &hit.symbol,
+                    );
+                }
+                return Ok(());
+            }
 
-            let mut results: Vec<(f32, String)> = stored_embeddings
-                .into_iter()
-                .map(|(doc, embedding)| {
-                    let score = cosine_similarity(query_embedding, &embedding);
-                    (score, doc)
-                })
-                .collect();
+            struct Span {
+                path: String,
+                start_line: usize,
+                end_line: usize,
+                start_byte: usize,
+                end_byte: usize,
+                text: String,
+                symbol: Option<String>,
+                embedding: Vec<f32>,
+            }
+            let mut spans = Vec::new();
+            db.for_each_span(|span| {
+                spans.push(Span {
+                    path: span.path,
+                    start_line: span.start_line,
+                    end_line: span.end_line,
+                    start_byte: span.start_byte,
+                    end_byte: span.end_byte,
+                    text: span.text,
+                    symbol: span.symbol,
+                    embedding: span.embedding,
+                });
+            })?;
 
-            results.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+            let vector_ranking = || -> Result<Vec<usize>, Box<dyn std::error::Error>> {
+                let embedder = build_provider(&provider, &model)?;
+                db.check_or_set_provider(&embedder.name())?;
+                let query_embeddings = embedder.embed(vec![query.clone()])?;
+                validate_dimensions(embedder.as_ref(), &query_embeddings)?;
+                let query_embedding = &query_embeddings[0];
+                let mut scored: Vec<(usize, f32)> = spans
+                    .iter()
+                    .enumerate()
+                    .map(|(i, span)| (i, cosine_similarity(query_embedding, &span.embedding)))
+                    .collect();
+                scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+                Ok(scored.into_iter().map(|(i, _)| i).collect())
+            };
 
-            let top_results = results.into_iter().take(top_k).collect::<Vec<_>>();
+            let keyword_ranking = || -> Vec<usize> {
+                let texts: Vec<String> = spans.iter().map(|span| span.text.clone()).collect();
+                Bm25Index::build(&texts)
+                    .search(&query)
+                    .into_iter()
+                    .map(|(i, _)| i)
+                    .collect()
+            };
 
-            for (_score, doc) in top_results {
-                println!("{}\n\n", doc.trim());
+            let ranking: Vec<usize> = match mode {
+                SearchMode::Vector => unreachable!("handled above"),
+                SearchMode::Keyword => keyword_ranking(),
+                SearchMode::Hybrid => {
+                    let rankings = [vector_ranking()?, keyword_ranking()];
+                    reciprocal_rank_fusion(&rankings, RRF_K)
+                        .into_iter()
+                        .map(|(i, _)| i)
+                        .collect()
+                }
+            };
+
+            for &i in ranking.iter().take(top_k) {
+                let span = &spans[i];
+                print_hit(
+                    &span.path,
+                    span.start_line,
+                    span.end_line,
+                    span.start_byte,
+                    span.end_byte,
+                    &span.symbol,
+                );
             }
         }
+        Command::Index { action } => match action {
+            IndexAction::Run {
+                pattern,
+                store,
+                provider,
+                model,
+                max_batch_tokens,
+                watch,
+                debounce_ms,
+            } => {
+                let mut db = Store::open(&store)?;
+                let config = IndexConfig {
+                    pattern,
+                    provider,
+                    model,
+                    max_batch_tokens,
+                };
+                if watch {
+                    println!("Watching for changes every {debounce_ms}ms (ctrl-c to stop)...");
+                    index::watch(&mut db, &config, std::time::Duration::from_millis(debounce_ms))?;
+                } else {
+                    let reindexed = index::index_once(&mut db, &config)?;
+                    println!(
+                        "Indexed {reindexed} changed file(s) into {}",
+                        store.display()
+                    );
+                }
+            }
+            IndexAction::Status { store } => {
+                let db = Store::open(&store)?;
+                for file in index::status(&db)? {
+                    let flag = if file.missing {
+                        " [missing]"
+                    } else if file.stale {
+                        " [stale]"
+                    } else {
+                        ""
+                    };
+                    println!("{} ({} chunks){flag}", file.path, file.chunk_count);
+                }
+            }
+        },
     }
     Ok(())
 }
@@ -0,0 +1,210 @@
+use fastembed::{EmbeddingModel, InitOptions, TextEmbedding};
+use serde::Deserialize;
+use std::env;
+
+/// A source of text embeddings. Implementations may run a model locally or
+/// call out to a hosted API; callers should not care which.
+pub trait EmbeddingProvider {
+    fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, Box<dyn std::error::Error>>;
+    fn dimensions(&self) -> usize;
+    /// Identifier persisted alongside embeddings so a `Query` run can refuse
+    /// to compare vectors produced by a different provider/model.
+    fn name(&self) -> String;
+}
+
+/// The original local model, backed by `fastembed`.
+pub struct FastEmbedProvider {
+    model: TextEmbedding,
+    model_name: String,
+    dimensions: usize,
+}
+
+impl FastEmbedProvider {
+    pub fn new(model_name: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let (embedding_model, dimensions) = resolve_fastembed_model(model_name)?;
+        let model = TextEmbedding::try_new(
+            InitOptions::new(embedding_model)
+                .with_show_download_progress(true)
+                .with_cache_dir(
+                    dirs::cache_dir()
+                        .expect("Could not get cache dir")
+                        .join("emqu-models"),
+                ),
+        )?;
+        Ok(Self {
+            model,
+            model_name: model_name.to_string(),
+            dimensions,
+        })
+    }
+}
+
+impl EmbeddingProvider for FastEmbedProvider {
+    fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, Box<dyn std::error::Error>> {
+        self.model.embed(texts, None)
+    }
+
+    fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    fn name(&self) -> String {
+        format!("fastembed:{}", self.model_name)
+    }
+}
+
+fn resolve_fastembed_model(
+    model_name: &str,
+) -> Result<(EmbeddingModel, usize), Box<dyn std::error::Error>> {
+    match model_name {
+        "AllMiniLML6V2" => Ok((EmbeddingModel::AllMiniLML6V2, 384)),
+        other => Err(format!("unknown fastembed model: {other}").into()),
+    }
+}
+
+/// Ollama's local HTTP embeddings endpoint (`POST /api/embeddings`).
+pub struct OllamaProvider {
+    base_url: String,
+    model_name: String,
+    client: reqwest::blocking::Client,
+}
+
+impl OllamaProvider {
+    pub fn new(model_name: &str) -> Self {
+        Self {
+            base_url: env::var("OLLAMA_HOST").unwrap_or_else(|_| "http://localhost:11434".into()),
+            model_name: model_name.to_string(),
+            client: reqwest::blocking::Client::new(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct OllamaEmbeddingResponse {
+    embedding: Vec<f32>,
+}
+
+impl EmbeddingProvider for OllamaProvider {
+    fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, Box<dyn std::error::Error>> {
+        texts
+            .into_iter()
+            .map(|text| {
+                let response: OllamaEmbeddingResponse = self
+                    .client
+                    .post(format!("{}/api/embeddings", self.base_url))
+                    .json(&serde_json::json!({ "model": self.model_name, "prompt": text }))
+                    .send()?
+                    .error_for_status()?
+                    .json()?;
+                Ok(response.embedding)
+            })
+            .collect()
+    }
+
+    fn dimensions(&self) -> usize {
+        // Ollama does not advertise dimensions up front; it is only known
+        // once the first embedding comes back.
+        0
+    }
+
+    fn name(&self) -> String {
+        format!("ollama:{}", self.model_name)
+    }
+}
+
+/// Any OpenAI-compatible `POST /v1/embeddings` endpoint, authenticated with
+/// an API key from the environment.
+pub struct OpenAiProvider {
+    base_url: String,
+    model_name: String,
+    api_key: String,
+    client: reqwest::blocking::Client,
+}
+
+impl OpenAiProvider {
+    pub fn new(model_name: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let api_key = env::var("OPENAI_API_KEY")
+            .map_err(|_| "OPENAI_API_KEY must be set to use the openai provider")?;
+        Ok(Self {
+            base_url: env::var("OPENAI_BASE_URL")
+                .unwrap_or_else(|_| "https://api.openai.com".into()),
+            model_name: model_name.to_string(),
+            api_key,
+            client: reqwest::blocking::Client::new(),
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct OpenAiEmbeddingData {
+    embedding: Vec<f32>,
+}
+
+#[derive(Deserialize)]
+struct OpenAiEmbeddingResponse {
+    data: Vec<OpenAiEmbeddingData>,
+}
+
+impl EmbeddingProvider for OpenAiProvider {
+    fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, Box<dyn std::error::Error>> {
+        let response: OpenAiEmbeddingResponse = self
+            .client
+            .post(format!("{}/v1/embeddings", self.base_url))
+            .bearer_auth(&self.api_key)
+            .json(&serde_json::json!({ "model": self.model_name, "input": texts }))
+            .send()?
+            .error_for_status()?
+            .json()?;
+        Ok(response.data.into_iter().map(|d| d.embedding).collect())
+    }
+
+    fn dimensions(&self) -> usize {
+        match self.model_name.as_str() {
+            "text-embedding-3-small" => 1536,
+            "text-embedding-3-large" => 3072,
+            _ => 0,
+        }
+    }
+
+    fn name(&self) -> String {
+        format!("openai:{}", self.model_name)
+    }
+}
+
+/// Builds the provider named on the CLI (`fastembed`, `ollama`, `openai`).
+pub fn build_provider(
+    provider: &str,
+    model: &str,
+) -> Result<Box<dyn EmbeddingProvider>, Box<dyn std::error::Error>> {
+    match provider {
+        "fastembed" => Ok(Box::new(FastEmbedProvider::new(model)?)),
+        "ollama" => Ok(Box::new(OllamaProvider::new(model))),
+        "openai" => Ok(Box::new(OpenAiProvider::new(model)?)),
+        other => Err(format!("unknown provider: {other}").into()),
+    }
+}
+
+/// Confirms every embedding in `embeddings` matches `provider`'s advertised
+/// dimensions, catching a misbehaving or misconfigured backend before a
+/// garbled vector gets written into the store. Providers that don't
+/// advertise a fixed dimension (returning `0`) skip the check.
+pub fn validate_dimensions(
+    provider: &dyn EmbeddingProvider,
+    embeddings: &[Vec<f32>],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let expected = provider.dimensions();
+    if expected == 0 {
+        return Ok(());
+    }
+    for embedding in embeddings {
+        if embedding.len() != expected {
+            return Err(format!(
+                "{} returned a {}-dimensional embedding, expected {expected}",
+                provider.name(),
+                embedding.len()
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
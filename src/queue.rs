@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+use tokenizers::Tokenizer;
+
+/// One item submitted to the queue: the text to embed and the index of the
+/// document it came from, so results can be reattached after batching and
+/// deduplication reorder everything.
+pub struct QueueItem {
+    pub index: usize,
+    pub text: String,
+}
+
+/// A batch of unique texts to hand to an `EmbeddingProvider`, together with
+/// the mapping back to every original document index that text belongs to.
+pub struct Batch {
+    /// Deduplicated texts, in the order they should be embedded.
+    pub texts: Vec<String>,
+    /// `occurrences[i]` lists every original document index whose text is
+    /// `texts[i]`, so one embed call can be fanned back out to all of them.
+    pub occurrences: Vec<Vec<usize>>,
+}
+
+/// Packs queue items into batches whose summed token count stays within
+/// `max_batch_tokens` (a single text larger than that gets a batch to
+/// itself), deduplicating identical texts within and across
+/// batches so a repeated document (e.g. two copies of a LICENSE file) is
+/// only ever embedded once.
+pub struct EmbeddingQueue {
+    tokenizer: Tokenizer,
+    max_batch_tokens: usize,
+}
+
+impl EmbeddingQueue {
+    pub fn new(tokenizer: Tokenizer, max_batch_tokens: usize) -> Self {
+        Self {
+            tokenizer,
+            max_batch_tokens,
+        }
+    }
+
+    fn token_count(&self, text: &str) -> usize {
+        self.tokenizer
+            .encode(text, false)
+            .map(|encoding| encoding.len())
+            .unwrap_or(0)
+    }
+
+    /// Builds batches for `items`, deduplicating identical texts first so
+    /// each unique string is only counted and embedded once.
+    pub fn batch(&self, items: Vec<QueueItem>) -> Vec<Batch> {
+        let mut unique_texts: Vec<String> = Vec::new();
+        let mut text_to_slot: HashMap<String, usize> = HashMap::new();
+        let mut occurrences: Vec<Vec<usize>> = Vec::new();
+
+        for item in items {
+            let slot = *text_to_slot.entry(item.text.clone()).or_insert_with(|| {
+                unique_texts.push(item.text.clone());
+                occurrences.push(Vec::new());
+                unique_texts.len() - 1
+            });
+            occurrences[slot].push(item.index);
+        }
+
+        let mut batches = Vec::new();
+        let mut current_texts = Vec::new();
+        let mut current_occurrences = Vec::new();
+        let mut current_tokens = 0usize;
+
+        for (text, occ) in unique_texts.into_iter().zip(occurrences) {
+            let tokens = self.token_count(&text);
+            if !current_texts.is_empty() && current_tokens + tokens > self.max_batch_tokens {
+                batches.push(Batch {
+                    texts: std::mem::take(&mut current_texts),
+                    occurrences: std::mem::take(&mut current_occurrences),
+                });
+                current_tokens = 0;
+            }
+            current_tokens += tokens;
+            current_texts.push(text);
+            current_occurrences.push(occ);
+        }
+
+        if !current_texts.is_empty() {
+            batches.push(Batch {
+                texts: current_texts,
+                occurrences: current_occurrences,
+            });
+        }
+
+        batches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap as Vocab;
+    use tokenizers::models::wordlevel::WordLevel;
+    use tokenizers::pre_tokenizers::whitespace::Whitespace;
+
+    /// A tokenizer whose token count is just its whitespace-split word
+    /// count, so tests can reason about `max_batch_tokens` in plain words.
+    fn word_counting_tokenizer() -> Tokenizer {
+        let vocab: Vocab<String, u32> = ["license", "readme", "notes", "[UNK]"]
+            .iter()
+            .enumerate()
+            .map(|(id, word)| (word.to_string(), id as u32))
+            .collect();
+        let model = WordLevel::builder()
+            .vocab(vocab)
+            .unk_token("[UNK]".to_string())
+            .build()
+            .unwrap();
+        let mut tokenizer = Tokenizer::new(model);
+        tokenizer.with_pre_tokenizer(Whitespace::default());
+        tokenizer
+    }
+
+    #[test]
+    fn dedups_identical_texts_across_items() {
+        let queue = EmbeddingQueue::new(word_counting_tokenizer(), 100);
+        let items = vec![
+            QueueItem { index: 0, text: "license".into() },
+            QueueItem { index: 1, text: "readme".into() },
+            QueueItem { index: 2, text: "license".into() },
+        ];
+
+        let batches = queue.batch(items);
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].texts, vec!["license".to_string(), "readme".to_string()]);
+        assert_eq!(batches[0].occurrences, vec![vec![0, 2], vec![1]]);
+    }
+
+    #[test]
+    fn splits_batches_once_the_token_budget_is_exceeded() {
+        let queue = EmbeddingQueue::new(word_counting_tokenizer(), 1);
+        let items = vec![
+            QueueItem { index: 0, text: "license".into() },
+            QueueItem { index: 1, text: "readme".into() },
+            QueueItem { index: 2, text: "notes".into() },
+        ];
+
+        let batches = queue.batch(items);
+        assert_eq!(batches.len(), 3);
+        assert_eq!(batches[0].texts, vec!["license".to_string()]);
+        assert_eq!(batches[1].texts, vec!["readme".to_string()]);
+        assert_eq!(batches[2].texts, vec!["notes".to_string()]);
+    }
+}
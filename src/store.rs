@@ -0,0 +1,249 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+/// A single embedded span of source text, as persisted in the `spans` table.
+pub struct SpanRow {
+    pub path: String,
+    pub start_byte: usize,
+    pub end_byte: usize,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub text: String,
+    pub symbol: Option<String>,
+    pub embedding: Vec<f32>,
+}
+
+/// A span awaiting a transactional write, produced by one completed
+/// embedding batch.
+pub struct PendingSpan {
+    pub path: String,
+    pub start_byte: usize,
+    pub end_byte: usize,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub digest: String,
+    pub text: String,
+    pub symbol: Option<String>,
+    pub embedding: Vec<f32>,
+}
+
+/// SQLite-backed embedding store, modeled on Zed's semantic index schema:
+/// a `files` table tracking per-path mtimes and a `spans` table holding one
+/// row per embedded chunk, with the embedding packed as a little-endian
+/// `f32` blob.
+pub struct Store {
+    conn: Connection,
+}
+
+impl Store {
+    pub fn open(path: impl AsRef<Path>) -> rusqlite::Result<Self> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS files (
+                path TEXT PRIMARY KEY,
+                mtime INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS spans (
+                id INTEGER PRIMARY KEY,
+                path TEXT NOT NULL,
+                start_byte INTEGER NOT NULL,
+                end_byte INTEGER NOT NULL,
+                start_line INTEGER NOT NULL,
+                end_line INTEGER NOT NULL,
+                digest TEXT NOT NULL,
+                text TEXT NOT NULL,
+                symbol TEXT,
+                embedding BLOB NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS meta (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS spans_digest ON spans(digest);
+            CREATE INDEX IF NOT EXISTS spans_path ON spans(path);",
+        )?;
+        Ok(Self { conn })
+    }
+
+    /// Records the provider+model name that produced this store's embeddings,
+    /// or confirms a fresh store matches if one is already set.
+    ///
+    /// Returns an error if the store was previously embedded with a
+    /// different provider/model, since mismatched dimensions would silently
+    /// produce garbage similarity scores.
+    pub fn check_or_set_provider(
+        &self,
+        provider_name: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let existing: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT value FROM meta WHERE key = 'provider'",
+                [],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        match existing {
+            Some(stored) if stored != provider_name => Err(format!(
+                "store was embedded with provider '{stored}', refusing to use '{provider_name}'"
+            )
+            .into()),
+            Some(_) => Ok(()),
+            None => {
+                self.conn.execute(
+                    "INSERT INTO meta (key, value) VALUES ('provider', ?1)",
+                    params![provider_name],
+                )?;
+                Ok(())
+            }
+        }
+    }
+
+    /// SHA-256 digest of a chunk's text, used as the dedup key across re-`Embed` runs.
+    pub fn digest(text: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(text.as_bytes());
+        format!("{:x}", hasher.finalize())
+    }
+
+    /// Returns the stored embedding of a span with `digest`, if one exists.
+    pub fn find_by_digest(&self, digest: &str) -> rusqlite::Result<Option<Vec<f32>>> {
+        self.conn
+            .query_row(
+                "SELECT embedding FROM spans WHERE digest = ?1 LIMIT 1",
+                params![digest],
+                |row| {
+                    let blob: Vec<u8> = row.get(0)?;
+                    Ok(unpack_embedding(&blob))
+                },
+            )
+            .optional()
+    }
+
+    /// Clears all existing spans for `paths`, so a re-`Embed` run can drop
+    /// stale chunks before writing a file's new ones across one or more
+    /// batches. Call this once per run, before any `commit_batch` calls for
+    /// those paths, since `commit_batch` only inserts.
+    pub fn clear_paths<'a>(&mut self, paths: impl Iterator<Item = &'a str>) -> rusqlite::Result<()> {
+        let tx = self.conn.transaction()?;
+        for path in paths {
+            tx.execute("DELETE FROM spans WHERE path = ?1", params![path])?;
+        }
+        tx.commit()
+    }
+
+    /// Writes every span in a completed batch in a single transaction, so a
+    /// failure partway through a batch never leaves that batch half written.
+    /// Does not clear prior spans for the path; call
+    /// `clear_paths` first for a full reindex.
+    ///
+    /// Deliberately does not touch the `files` table: a file's spans can be
+    /// split across several batches, and recording its mtime here would mark
+    /// it fully indexed after only the first of those batches lands. Call
+    /// `mark_files_indexed` once all of a run's batches have committed.
+    pub fn commit_batch(&mut self, spans: &[PendingSpan]) -> rusqlite::Result<()> {
+        let tx = self.conn.transaction()?;
+        for span in spans {
+            tx.execute(
+                "INSERT INTO spans (path, start_byte, end_byte, start_line, end_line, digest, text, symbol, embedding)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    span.path,
+                    span.start_byte as i64,
+                    span.end_byte as i64,
+                    span.start_line as i64,
+                    span.end_line as i64,
+                    span.digest,
+                    span.text,
+                    span.symbol,
+                    pack_embedding(&span.embedding)
+                ],
+            )?;
+        }
+        tx.commit()
+    }
+
+    /// Records `(path, mtime)` pairs as fully indexed. Call this only after
+    /// every batch touching these paths has been committed, so a crash
+    /// mid-run leaves an unfinished file looking stale (and eligible for
+    /// re-indexing) rather than falsely up to date.
+    pub fn mark_files_indexed(&mut self, files: &[(String, i64)]) -> rusqlite::Result<()> {
+        let tx = self.conn.transaction()?;
+        for (path, mtime) in files {
+            tx.execute(
+                "INSERT INTO files (path, mtime) VALUES (?1, ?2)
+                 ON CONFLICT(path) DO UPDATE SET mtime = excluded.mtime",
+                params![path, mtime],
+            )?;
+        }
+        tx.commit()
+    }
+
+    /// Returns the mtime recorded for `path` the last time it was indexed,
+    /// if any.
+    pub fn file_mtime(&self, path: &str) -> rusqlite::Result<Option<i64>> {
+        self.conn
+            .query_row(
+                "SELECT mtime FROM files WHERE path = ?1",
+                params![path],
+                |row| row.get(0),
+            )
+            .optional()
+    }
+
+    /// Lists every indexed path with its recorded mtime and span count, for
+    /// `index status`.
+    pub fn file_summaries(&self) -> rusqlite::Result<Vec<(String, i64, usize)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT files.path, files.mtime, COUNT(spans.id)
+             FROM files
+             LEFT JOIN spans ON spans.path = files.path
+             GROUP BY files.path
+             ORDER BY files.path",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, i64>(2)? as usize,
+            ))
+        })?;
+        rows.collect()
+    }
+
+    /// Streams every stored span to `f`, so callers can score against a query
+    /// embedding without holding the whole store in memory.
+    pub fn for_each_span(&self, mut f: impl FnMut(SpanRow)) -> rusqlite::Result<()> {
+        let mut stmt = self.conn.prepare(
+            "SELECT path, start_byte, end_byte, start_line, end_line, text, symbol, embedding FROM spans",
+        )?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let blob: Vec<u8> = row.get(7)?;
+            f(SpanRow {
+                path: row.get(0)?,
+                start_byte: row.get::<_, i64>(1)? as usize,
+                end_byte: row.get::<_, i64>(2)? as usize,
+                start_line: row.get::<_, i64>(3)? as usize,
+                end_line: row.get::<_, i64>(4)? as usize,
+                text: row.get(5)?,
+                symbol: row.get(6)?,
+                embedding: unpack_embedding(&blob),
+            });
+        }
+        Ok(())
+    }
+}
+
+fn pack_embedding(embedding: &[f32]) -> Vec<u8> {
+    embedding.iter().flat_map(|f| f.to_le_bytes()).collect()
+}
+
+fn unpack_embedding(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()))
+        .collect()
+}